@@ -1,16 +1,248 @@
 use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::fmt;
 use std::fs;
-use std::path::Path;
-use std::process::Command;
+use std::io;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::RawFd;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::Duration;
 use tempfile::TempDir;
 
+pub mod ssh;
+
 pub trait CommandExecutor {
     fn execute<P: AsRef<Path>>(
         &self,
         command: &[String],
         files: &[P],
         working_dir: &Path,
-    ) -> Result<()>;
+        options: ExecutionOptions,
+    ) -> Result<SandboxOutput>;
+}
+
+/// Everything about *how* a command runs, as opposed to *what* it is or
+/// *where* its files come from. Bundled into one struct so `execute` doesn't
+/// keep growing a parameter per knob.
+#[derive(Default)]
+pub struct ExecutionOptions {
+    pub policy: SandboxPolicy,
+    pub environment: Environment,
+    pub stdin: Option<Vec<u8>>,
+    pub timeout: Option<Duration>,
+}
+
+/// Walks `root` and returns the paths of every file matching `patterns`,
+/// suitable for passing straight into [`CommandExecutor::execute`] as
+/// `files` instead of hand-listing them.
+///
+/// Honors `.gitignore` and `.fdignore` exclusion files the same way `fd`
+/// does. Symlinks that resolve outside `root` are skipped rather than
+/// followed, so the sandbox never silently pulls in out-of-tree data; other
+/// symlinks are kept and copied by following them, same as a plain file. A
+/// symlink to a directory is recursed into (subject to the same escape
+/// check) rather than returned as-is, since it isn't a file itself.
+pub fn capture_dir(root: &Path, patterns: &[&str]) -> Result<Vec<PathBuf>> {
+    let mut globset_builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        globset_builder
+            .add(Glob::new(pattern).with_context(|| format!("Invalid glob pattern: {}", pattern))?);
+    }
+    let globset = globset_builder.build().context("Failed to build glob set")?;
+
+    let canonical_root = root
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve capture root: {:?}", root))?;
+
+    let mut files = Vec::new();
+    let mut visited = HashSet::new();
+    visited.insert(canonical_root.clone());
+    collect_files(
+        root,
+        root,
+        root,
+        &canonical_root,
+        patterns,
+        &globset,
+        &mut visited,
+        &mut files,
+    )?;
+
+    Ok(files)
+}
+
+/// Walks `disk_dir` (the real directory to read) and reports matching files
+/// under `display_dir` (the path they should be returned as — the same as
+/// `disk_dir` unless we're recursing through a symlink, in which case it's
+/// the symlink's own path so callers see the name they asked for instead of
+/// the resolved target).
+#[allow(clippy::too_many_arguments)]
+fn collect_files(
+    disk_dir: &Path,
+    display_dir: &Path,
+    root: &Path,
+    canonical_root: &Path,
+    patterns: &[&str],
+    globset: &GlobSet,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let mut walk_builder = WalkBuilder::new(disk_dir);
+    walk_builder.add_custom_ignore_filename(".fdignore");
+
+    for entry in walk_builder.build() {
+        let entry = entry.context("Failed to walk capture directory")?;
+        let path = entry.path();
+        let relative = path.strip_prefix(disk_dir).unwrap_or(path);
+        let display_path = if relative.as_os_str().is_empty() {
+            display_dir.to_path_buf()
+        } else {
+            display_dir.join(relative)
+        };
+
+        if entry.path_is_symlink() {
+            let target = match path.canonicalize() {
+                Ok(target) => target,
+                Err(_) => continue, // broken symlink; nothing to capture
+            };
+            if !target.starts_with(canonical_root) {
+                continue; // escapes the capture root
+            }
+            if target.is_dir() {
+                if !visited.insert(target.clone()) {
+                    continue; // already walked via another symlink; avoid an infinite loop
+                }
+                collect_files(
+                    &target,
+                    &display_path,
+                    root,
+                    canonical_root,
+                    patterns,
+                    globset,
+                    visited,
+                    files,
+                )?;
+                continue;
+            }
+        } else if entry.file_type().is_some_and(|file_type| file_type.is_dir()) {
+            continue;
+        }
+
+        let relative_to_root = display_path.strip_prefix(root).unwrap_or(&display_path);
+        if !patterns.is_empty() && !globset.is_match(relative_to_root) {
+            continue;
+        }
+
+        files.push(display_path);
+    }
+
+    Ok(())
+}
+
+/// Controls what environment variables the sandboxed child sees.
+///
+/// Defaults to [`Environment::InheritWithAllowlist`] with a minimal safe set
+/// (`PATH`, `HOME`, `LANG`) so sandboxes are hermetic unless a caller opts
+/// into more.
+pub enum Environment {
+    /// Inherit the parent process's environment unchanged.
+    InheritAll,
+    /// Clear the environment entirely and set exactly these variables.
+    ClearAll(HashMap<String, String>),
+    /// Clear the environment, then copy through only the named variables
+    /// that are set in the parent's environment.
+    InheritWithAllowlist(Vec<String>),
+}
+
+impl Environment {
+    fn apply(&self, cmd: &mut Command) {
+        match self {
+            Environment::InheritAll => {}
+            Environment::ClearAll(vars) => {
+                cmd.env_clear();
+                cmd.envs(vars);
+            }
+            Environment::InheritWithAllowlist(names) => {
+                cmd.env_clear();
+                for name in names {
+                    if let Ok(value) = std::env::var(name) {
+                        cmd.env(name, value);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::InheritWithAllowlist(
+            ["PATH", "HOME", "LANG"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        )
+    }
+}
+
+/// The result of running a sandboxed command: its exit status and captured
+/// output, so callers can inspect both without a non-zero exit being treated
+/// as an error.
+pub struct SandboxOutput {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// The command ran longer than the caller's `timeout` and was killed.
+#[derive(Debug)]
+pub struct TimedOut {
+    pub timeout: Duration,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "command timed out after {:?}", self.timeout)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Controls what privileges the sandboxed child is allowed to keep.
+///
+/// By default supplementary groups are dropped; `run_as` is `None`, meaning
+/// the child keeps whatever id the namespace setup mapped it to.
+pub struct SandboxPolicy {
+    /// If set, the child is moved to this (uid, gid) after the namespace and
+    /// chroot are in place.
+    pub run_as: Option<(u32, u32)>,
+    /// Reserved for future policies that might keep the caller's
+    /// supplementary groups. Currently always effectively `true`: any group
+    /// that isn't in the user namespace's gid map has no translation inside
+    /// it, so the namespace setup itself already strips supplementary
+    /// groups unconditionally.
+    pub clear_groups: bool,
+}
+
+impl SandboxPolicy {
+    pub fn new() -> Self {
+        SandboxPolicy {
+            run_as: None,
+            clear_groups: true,
+        }
+    }
+}
+
+impl Default for SandboxPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 pub struct LinuxCommandExecutor;
@@ -21,13 +253,27 @@ impl LinuxCommandExecutor {
     }
 }
 
+impl Default for LinuxCommandExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CommandExecutor for LinuxCommandExecutor {
     fn execute<P: AsRef<Path>>(
         &self,
         command: &[String],
         files: &[P],
         working_dir: &Path,
-    ) -> Result<()> {
+        options: ExecutionOptions,
+    ) -> Result<SandboxOutput> {
+        let ExecutionOptions {
+            policy,
+            environment,
+            stdin,
+            timeout,
+        } = options;
+
         // Create a temporary directory
         let temp_dir = TempDir::new().context("Failed to create temporary directory")?;
 
@@ -49,25 +295,298 @@ impl CommandExecutor for LinuxCommandExecutor {
                 .with_context(|| format!("Failed to copy file: {:?}", file_path))?;
         }
 
+        // Everything the pre_exec closure needs has to be prepared here, in the
+        // parent, because the closure runs after fork but before exec and must
+        // stay async-signal-safe (no heap allocation, no libstd calls that may
+        // allocate or take locks).
+        let namespace_setup = NamespaceSetup::new(temp_dir.path(), &policy)?;
+
         // Prepare the command
         let mut cmd = Command::new(&command[0]);
-        cmd.args(&command[1..]).current_dir(temp_dir.path());
+        cmd.args(&command[1..]).current_dir("/");
+        environment.apply(&mut cmd);
 
-        // Execute the command
-        let output = cmd.output().context("Failed to execute command")?;
+        unsafe {
+            cmd.pre_exec(move || namespace_setup.apply());
+        }
 
-        // Check if the command was successful
-        if output.status.success() {
-            println!("Command executed successfully");
-            println!("Output: {}", String::from_utf8_lossy(&output.stdout));
-            Ok(())
-        } else {
-            let error_message = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("Command failed: {}", error_message)
+        // Execute the command, enforcing the timeout if one was given
+        let output = run_with_timeout(&mut cmd, stdin, timeout)?;
+
+        Ok(SandboxOutput {
+            status: output.status,
+            stdout: output.stdout,
+            stderr: output.stderr,
+        })
+    }
+}
+
+/// Everything the `pre_exec` closure needs, pre-built in the parent so the
+/// closure itself only ever makes raw syscalls.
+struct NamespaceSetup {
+    root: CString,
+    setgroups_path: CString,
+    uid_map_path: CString,
+    gid_map_path: CString,
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    run_as: Option<(u32, u32)>,
+}
+
+impl NamespaceSetup {
+    fn new(root: &Path, policy: &SandboxPolicy) -> Result<Self> {
+        // Map the outer uid/gid to root inside the new user namespace so the
+        // subsequent mount/chroot calls are permitted without real privileges.
+        // If the policy wants to run as a different id afterwards, give that
+        // id an entry too so `setresuid`/`setresgid` in `apply` has something
+        // to move into.
+        let outer_uid = unsafe { libc::getuid() };
+        let outer_gid = unsafe { libc::getgid() };
+
+        let mut uid_map = format!("0 {} 1\n", outer_uid);
+        let mut gid_map = format!("0 {} 1\n", outer_gid);
+        if let Some((run_as_uid, run_as_gid)) = policy.run_as {
+            if run_as_uid != 0 {
+                uid_map.push_str(&format!("{} {} 1\n", run_as_uid, outer_uid));
+            }
+            if run_as_gid != 0 {
+                gid_map.push_str(&format!("{} {} 1\n", run_as_gid, outer_gid));
+            }
+        }
+
+        Ok(NamespaceSetup {
+            root: path_to_cstring(root)?,
+            setgroups_path: CString::new("/proc/self/setgroups").unwrap(),
+            uid_map_path: CString::new("/proc/self/uid_map").unwrap(),
+            gid_map_path: CString::new("/proc/self/gid_map").unwrap(),
+            uid_map: uid_map.into_bytes(),
+            gid_map: gid_map.into_bytes(),
+            run_as: policy.run_as,
+        })
+    }
+
+    /// Runs in the child after `fork` but before `exec`. Must stay
+    /// async-signal-safe: every string used here was allocated in `new`, so
+    /// this only issues raw syscalls.
+    ///
+    /// Note this does *not* isolate the PID namespace: `CLONE_NEWPID` only
+    /// takes effect for children created after the call, and since this
+    /// process execs directly instead of forking again, it never moves into
+    /// a new PID namespace itself. Filesystem and network isolation (via
+    /// `CLONE_NEWUSER`/`CLONE_NEWNS`/`CLONE_NEWNET`) apply immediately to the
+    /// calling process and do take effect.
+    fn apply(&self) -> io::Result<()> {
+        unsafe {
+            if libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWNET) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            // `setgroups` must be denied before the gid_map write is allowed
+            // for an unprivileged user namespace.
+            write_raw(&self.setgroups_path, b"deny")?;
+            write_raw(&self.uid_map_path, &self.uid_map)?;
+            write_raw(&self.gid_map_path, &self.gid_map)?;
+
+            // Make the mount namespace private so the bind mount below
+            // doesn't propagate back out to the host.
+            if libc::mount(
+                std::ptr::null(),
+                c"/".as_ptr(),
+                std::ptr::null(),
+                libc::MS_PRIVATE | libc::MS_REC,
+                std::ptr::null(),
+            ) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+
+            // Bind-mount the sandbox dir onto itself so it's a mount point,
+            // which `chroot` requires for it to become the new root.
+            if libc::mount(
+                self.root.as_ptr(),
+                self.root.as_ptr(),
+                std::ptr::null(),
+                libc::MS_BIND | libc::MS_REC,
+                std::ptr::null(),
+            ) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+
+            if libc::chroot(self.root.as_ptr()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::chdir(c"/".as_ptr()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            // Supplementary groups are already effectively dropped: writing
+            // "deny" to setgroups above (required so the gid_map write is
+            // permitted without CAP_SETGID) also permanently disables
+            // setgroups(2) for the rest of this user namespace's life, so a
+            // second call here to honor `clear_groups` would just fail with
+            // EPERM. Any supplementary group the caller held that isn't in
+            // gid_map has no mapping in this namespace anyway, so it's
+            // already unusable. Move to the requested id, in gid-then-uid
+            // order so we never hold a dropped uid's privileges while still
+            // being able to change the gid.
+            if let Some((run_as_uid, run_as_gid)) = self.run_as {
+                if libc::setresgid(run_as_gid, run_as_gid, run_as_gid) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                if libc::setresuid(run_as_uid, run_as_uid, run_as_uid) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Spawns `cmd`, capturing stdout/stderr, and kills it if it outlives
+/// `timeout`. Uses a pidfd rather than polling `try_wait` so completion and
+/// the deadline can be waited on together without a race, and so the kill
+/// targets the exact child even if its pid gets reused.
+fn run_with_timeout(
+    cmd: &mut Command,
+    stdin: Option<Vec<u8>>,
+    timeout: Option<Duration>,
+) -> Result<std::process::Output> {
+    cmd.stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = cmd.spawn().context("Failed to spawn command")?;
+
+    // Write stdin on its own thread, concurrently with draining stdout and
+    // stderr below. A child that echoes what it reads can fill its stdout
+    // pipe before we've read any of it; writing all of stdin synchronously
+    // first would then deadlock (it blocks on a full stdin write while the
+    // child blocks on a full stdout write nobody is reading).
+    let stdin_pipe = child.stdin.take().context("Child stdin was not piped")?;
+    let stdin_writer = std::thread::spawn(move || -> io::Result<()> {
+        let mut stdin_pipe = stdin_pipe;
+        let Some(input) = stdin else {
+            return Ok(());
+        };
+        match stdin_pipe.write_all(&input) {
+            Ok(()) => Ok(()),
+            // The child exiting without reading all of stdin is normal
+            // (e.g. `head -1`), not a failure of the command.
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            Err(err) => Err(err),
+        }
+    });
+
+    let result = (|| -> Result<std::process::Output> {
+        let Some(timeout) = timeout else {
+            return child
+                .wait_with_output()
+                .context("Failed to wait for command");
+        };
+
+        let pidfd = Pidfd::open(child.id()).context("Failed to open pidfd for spawned child")?;
+
+        if pidfd.wait_readable(timeout).context("Failed to poll pidfd")? {
+            return child
+                .wait_with_output()
+                .context("Failed to wait for command");
+        }
+
+        pidfd
+            .kill()
+            .context("Failed to send SIGKILL through pidfd")?;
+        // The child is dead or dying; reap it so it doesn't linger as a zombie.
+        child.wait().context("Failed to reap timed-out child")?;
+        Err(TimedOut { timeout }.into())
+    })();
+
+    match stdin_writer.join() {
+        Ok(write_result) => write_result.context("Failed to write to child stdin")?,
+        Err(_) => anyhow::bail!("stdin writer thread panicked"),
+    }
+
+    result
+}
+
+/// A `pidfd(2)` handle: a stable, race-free reference to a specific process
+/// that keeps working even if its pid is reused after it exits.
+struct Pidfd(RawFd);
+
+impl Pidfd {
+    fn open(pid: u32) -> io::Result<Self> {
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Pidfd(fd as RawFd))
+    }
+
+    /// Blocks until the process exits or `timeout` elapses. Returns `true`
+    /// if the process exited, `false` on timeout.
+    fn wait_readable(&self, timeout: Duration) -> io::Result<bool> {
+        let mut pollfd = libc::pollfd {
+            fd: self.0,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout_ms = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+
+        let ready = unsafe { libc::poll(&mut pollfd, 1, timeout_ms) };
+        if ready < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ready > 0)
+    }
+
+    fn kill(&self) -> io::Result<()> {
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_pidfd_send_signal,
+                self.0,
+                libc::SIGKILL,
+                std::ptr::null::<libc::c_void>(),
+                0,
+            )
+        };
+        if result != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Pidfd {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
         }
     }
 }
 
+unsafe fn write_raw(path: &CString, contents: &[u8]) -> io::Result<()> {
+    let fd = libc::open(path.as_ptr(), libc::O_WRONLY);
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let written = libc::write(fd, contents.as_ptr() as *const libc::c_void, contents.len());
+    let close_result = libc::close(fd);
+
+    if written < 0 || written as usize != contents.len() {
+        return Err(io::Error::last_os_error());
+    }
+    if close_result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+fn path_to_cstring(path: &Path) -> Result<CString> {
+    CString::new(path.as_os_str().as_bytes()).context("sandbox path contains a NUL byte")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,13 +617,317 @@ mod tests {
             absolute_file_path.to_str().unwrap().to_string(),
         ];
 
-        // 5. Expect the command to fail (but it will actually succeed)
-        let result = executor.execute::<std::path::PathBuf>(&command, &[], temp_path);
+        // 5. Neither `cat` nor the file lives inside the sandbox root, and the
+        // chroot put in place by `NamespaceSetup` makes both unreachable: the
+        // command cannot even be spawned, so `execute` returns an error. (A
+        // command that ran and merely exited non-zero would come back as `Ok`.)
+        let result = executor.execute::<std::path::PathBuf>(
+            &command,
+            &[],
+            temp_path,
+            ExecutionOptions::default(),
+        );
 
-        // This assertion will fail because the command succeeds
         assert!(
             result.is_err(),
             "Expected command to fail, but it succeeded"
         );
     }
+
+    /// Compiles a tiny statically-linked `cat`-alike at `dest` so sandbox
+    /// tests have an executable that actually runs once chrooted: the
+    /// sandbox root only ever contains what's passed in via `files`, so a
+    /// normal dynamically-linked system binary like `/bin/cat` can't resolve
+    /// its loader or shared libraries inside it.
+    fn build_static_cat(dest: &Path) {
+        let source = dest.with_extension("c");
+        fs::write(
+            &source,
+            r#"
+            #include <stdio.h>
+            int main(int argc, char **argv) {
+                FILE *f = fopen(argv[1], "r");
+                if (!f) { return 1; }
+                char buf[4096];
+                size_t n;
+                while ((n = fread(buf, 1, sizeof(buf), f)) > 0) {
+                    fwrite(buf, 1, n, stdout);
+                }
+                return 0;
+            }
+            "#,
+        )
+        .unwrap();
+
+        let status = Command::new("cc")
+            .args(["-static", "-o"])
+            .arg(dest)
+            .arg(&source)
+            .status()
+            .expect("failed to invoke cc to build the test fixture binary");
+        assert!(status.success(), "cc failed to build the test fixture binary");
+    }
+
+    #[test]
+    fn test_linux_command_executor_in_sandbox_file_succeeds() {
+        // The companion happy-path to `test_linux_command_executor_file_access`
+        // above: a file that *is* copied into the sandbox root must still be
+        // readable, so a broken namespace/mount/chroot setup can't pass the
+        // escaping-path test by failing for the wrong reason.
+        //
+        // The sandbox root only ever contains what's explicitly passed in via
+        // `files`, so the command itself has to be one of them too — a
+        // dynamically-linked system binary like `/bin/cat` has no loader or
+        // shared libraries to resolve once chrooted. A small statically
+        // linked fixture binary stands in for `cat` here.
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path();
+
+        let file_name = "in_sandbox.txt";
+        let file_content = "hello from inside the sandbox";
+        let file_path = temp_path.join(file_name);
+        let mut file = File::create(&file_path).unwrap();
+        write!(file, "{}", file_content).unwrap();
+
+        let cat_bin_path = temp_path.join("static_cat");
+        build_static_cat(&cat_bin_path);
+
+        let executor = LinuxCommandExecutor::new();
+        let command = vec!["/static_cat".to_string(), file_name.to_string()];
+
+        let output = executor
+            .execute(
+                &command,
+                &[file_path, cat_bin_path],
+                temp_path,
+                ExecutionOptions::default(),
+            )
+            .expect("a command over a file copied into the sandbox root should run");
+
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), file_content);
+    }
+
+    #[test]
+    fn run_with_timeout_does_not_deadlock_on_large_echoed_stdin() {
+        // Bigger than a pipe buffer (64 KiB on Linux), so `cat` will block
+        // trying to write it back out unless we're also draining stdout
+        // concurrently with writing stdin.
+        let input = vec![b'x'; 256 * 1024];
+        let mut cmd = Command::new("cat");
+
+        let output = run_with_timeout(&mut cmd, Some(input.clone()), None).unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(output.stdout, input);
+    }
+
+    #[test]
+    fn run_with_timeout_kills_and_errors_on_timeout() {
+        let mut cmd = Command::new("sleep");
+        cmd.arg("5");
+
+        let err = run_with_timeout(&mut cmd, None, Some(Duration::from_millis(100))).unwrap_err();
+
+        assert!(err.downcast_ref::<TimedOut>().is_some());
+    }
+
+    #[test]
+    fn capture_dir_honors_gitignore_and_glob_patterns() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        // As with fd, `.gitignore` only applies inside a git repository. The
+        // ignored file matches the glob, so only the `.gitignore` excludes it.
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::write(root.join(".gitignore"), "ignored.rs\n").unwrap();
+        fs::write(root.join("ignored.rs"), "should not be captured").unwrap();
+        fs::write(root.join("keep.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("keep.txt"), "not matched by the glob").unwrap();
+
+        let files = capture_dir(root, &["*.rs"]).unwrap();
+
+        assert_eq!(files, vec![root.join("keep.rs")]);
+    }
+
+    #[test]
+    fn capture_dir_honors_fdignore() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        fs::write(root.join(".fdignore"), "secret.txt\n").unwrap();
+        fs::write(root.join("secret.txt"), "should not be captured").unwrap();
+        fs::write(root.join("public.txt"), "should be captured").unwrap();
+
+        let files = capture_dir(root, &[]).unwrap();
+
+        assert_eq!(files, vec![root.join("public.txt")]);
+    }
+
+    #[test]
+    fn capture_dir_skips_symlinks_that_escape_the_root_but_keeps_ones_that_dont() {
+        let temp_dir = TempDir::new().unwrap();
+        let root_dir = TempDir::new().unwrap();
+        let root = root_dir.path();
+
+        let outside_file = temp_dir.path().join("outside.txt");
+        fs::write(&outside_file, "outside the capture root").unwrap();
+        std::os::unix::fs::symlink(&outside_file, root.join("escaping_link.txt")).unwrap();
+
+        fs::write(root.join("inside.txt"), "inside the capture root").unwrap();
+        std::os::unix::fs::symlink(root.join("inside.txt"), root.join("inside_link.txt")).unwrap();
+
+        let mut files = capture_dir(root, &[]).unwrap();
+        files.sort();
+
+        let mut expected = vec![root.join("inside.txt"), root.join("inside_link.txt")];
+        expected.sort();
+        assert_eq!(files, expected);
+    }
+
+    #[test]
+    fn capture_dir_recurses_into_a_symlinked_subdirectory() {
+        // Mirrors a vendored/.bin-style tree where a subdir is a symlink to
+        // shared content elsewhere in the same tree: the symlink itself
+        // isn't a file, so it must be walked rather than returned as-is.
+        let root_dir = TempDir::new().unwrap();
+        let root = root_dir.path();
+        fs::write(root.join("main.rs"), "fn main() {}").unwrap();
+
+        fs::create_dir(root.join("real_vendor")).unwrap();
+        fs::write(root.join("real_vendor").join("vendored.rs"), "fn vendored() {}").unwrap();
+        std::os::unix::fs::symlink(root.join("real_vendor"), root.join("vendor")).unwrap();
+
+        let mut files = capture_dir(root, &[]).unwrap();
+        files.sort();
+
+        let mut expected = vec![
+            root.join("main.rs"),
+            root.join("real_vendor").join("vendored.rs"),
+            root.join("vendor").join("vendored.rs"),
+        ];
+        expected.sort();
+        assert_eq!(files, expected);
+    }
+
+    #[test]
+    fn capture_dir_skips_a_symlinked_subdirectory_that_escapes_the_root() {
+        let real_dir = TempDir::new().unwrap();
+        fs::write(real_dir.path().join("secret.txt"), "outside the capture root").unwrap();
+
+        let root_dir = TempDir::new().unwrap();
+        let root = root_dir.path();
+        fs::write(root.join("main.rs"), "fn main() {}").unwrap();
+        std::os::unix::fs::symlink(real_dir.path(), root.join("escaping_dir")).unwrap();
+
+        let files = capture_dir(root, &[]).unwrap();
+
+        assert_eq!(files, vec![root.join("main.rs")]);
+    }
+
+    #[test]
+    fn environment_inherit_all_leaves_the_command_env_untouched() {
+        let mut cmd = Command::new("true");
+        cmd.env("PROBE_VAR", "probe_value");
+
+        Environment::InheritAll.apply(&mut cmd);
+
+        assert!(cmd
+            .get_envs()
+            .any(|(key, value)| key == "PROBE_VAR" && value == Some(std::ffi::OsStr::new("probe_value"))));
+    }
+
+    #[test]
+    fn environment_clear_all_only_sets_the_given_vars() {
+        let mut cmd = Command::new("true");
+        cmd.env("SHOULD_NOT_SURVIVE", "x");
+
+        let mut vars = HashMap::new();
+        vars.insert("FOO".to_string(), "bar".to_string());
+        Environment::ClearAll(vars).apply(&mut cmd);
+
+        let envs: Vec<_> = cmd.get_envs().collect();
+        assert_eq!(
+            envs,
+            vec![(std::ffi::OsStr::new("FOO"), Some(std::ffi::OsStr::new("bar")))]
+        );
+    }
+
+    #[test]
+    fn environment_allowlist_only_copies_through_named_vars_that_are_set() {
+        // SAFETY: test runs single-threaded within this process; no other
+        // thread reads/writes these env vars concurrently.
+        unsafe {
+            std::env::set_var("SANDBOX_RS_TEST_ALLOWED", "allowed_value");
+            std::env::remove_var("SANDBOX_RS_TEST_NOT_SET");
+        }
+
+        let mut cmd = Command::new("true");
+        Environment::InheritWithAllowlist(vec![
+            "SANDBOX_RS_TEST_ALLOWED".to_string(),
+            "SANDBOX_RS_TEST_NOT_SET".to_string(),
+        ])
+        .apply(&mut cmd);
+
+        let envs: Vec<_> = cmd.get_envs().collect();
+        assert_eq!(
+            envs,
+            vec![(
+                std::ffi::OsStr::new("SANDBOX_RS_TEST_ALLOWED"),
+                Some(std::ffi::OsStr::new("allowed_value"))
+            )]
+        );
+
+        // SAFETY: see above.
+        unsafe {
+            std::env::remove_var("SANDBOX_RS_TEST_ALLOWED");
+        }
+    }
+
+    #[test]
+    fn namespace_setup_maps_outer_id_to_root_by_default() {
+        let setup = NamespaceSetup::new(Path::new("/tmp"), &SandboxPolicy::default()).unwrap();
+
+        let outer_uid = unsafe { libc::getuid() };
+        let outer_gid = unsafe { libc::getgid() };
+
+        assert_eq!(setup.uid_map, format!("0 {outer_uid} 1\n").into_bytes());
+        assert_eq!(setup.gid_map, format!("0 {outer_gid} 1\n").into_bytes());
+    }
+
+    #[test]
+    fn namespace_setup_adds_a_mapping_line_for_a_nonzero_run_as_id() {
+        let policy = SandboxPolicy {
+            run_as: Some((1000, 1000)),
+            clear_groups: true,
+        };
+        let setup = NamespaceSetup::new(Path::new("/tmp"), &policy).unwrap();
+
+        let outer_uid = unsafe { libc::getuid() };
+        let outer_gid = unsafe { libc::getgid() };
+
+        assert_eq!(
+            setup.uid_map,
+            format!("0 {outer_uid} 1\n1000 {outer_uid} 1\n").into_bytes()
+        );
+        assert_eq!(
+            setup.gid_map,
+            format!("0 {outer_gid} 1\n1000 {outer_gid} 1\n").into_bytes()
+        );
+    }
+
+    #[test]
+    fn namespace_setup_does_not_duplicate_the_root_mapping_for_run_as_root() {
+        let policy = SandboxPolicy {
+            run_as: Some((0, 0)),
+            clear_groups: true,
+        };
+        let setup = NamespaceSetup::new(Path::new("/tmp"), &policy).unwrap();
+
+        let outer_uid = unsafe { libc::getuid() };
+        let outer_gid = unsafe { libc::getgid() };
+
+        assert_eq!(setup.uid_map, format!("0 {outer_uid} 1\n").into_bytes());
+        assert_eq!(setup.gid_map, format!("0 {outer_gid} 1\n").into_bytes());
+    }
 }
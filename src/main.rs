@@ -1,16 +1,19 @@
 use anyhow::Result;
-use sandbox_rs::{CommandExecutor, LinuxCommandExecutor};
+use sandbox_rs::{CommandExecutor, ExecutionOptions, LinuxCommandExecutor};
 use std::env;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 fn main() -> Result<()> {
     let current_dir = env::current_dir()?;
-    let executor = LinuxCommandExecutor::new(&current_dir);
+    let executor = LinuxCommandExecutor::new();
 
     let this_file = Path::new(std::file!());
     let full_file_path = current_dir.join(this_file);
     let command = vec!["cat".to_string(), this_file.to_str().unwrap().to_string()];
     let files = vec![full_file_path];
 
-    executor.execute(&command, &files)
+    let output = executor.execute(&command, &files, &current_dir, ExecutionOptions::default())?;
+    print!("{}", String::from_utf8_lossy(&output.stdout));
+
+    Ok(())
 }
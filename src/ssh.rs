@@ -0,0 +1,302 @@
+//! A [`CommandExecutor`] backend that runs the sandboxed command on a
+//! remote host over SSH instead of in a local chroot. Files are uploaded
+//! over SFTP into a disposable remote temp dir, the command runs there, and
+//! the dir is removed afterwards.
+
+use crate::{CommandExecutor, Environment, ExecutionOptions, SandboxOutput};
+use anyhow::{Context, Result};
+use ssh2::Session;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::Duration;
+
+/// How to authenticate the SSH connection.
+pub enum SshAuth {
+    Password(String),
+    PrivateKeyFile {
+        path: PathBuf,
+        passphrase: Option<String>,
+    },
+}
+
+pub struct SshCommandExecutor {
+    host: String,
+    port: u16,
+    username: String,
+    auth: SshAuth,
+}
+
+impl SshCommandExecutor {
+    pub fn new(host: impl Into<String>, port: u16, username: impl Into<String>, auth: SshAuth) -> Self {
+        SshCommandExecutor {
+            host: host.into(),
+            port,
+            username: username.into(),
+            auth,
+        }
+    }
+
+    fn connect(&self, timeout: Option<Duration>) -> Result<Session> {
+        let tcp = TcpStream::connect((self.host.as_str(), self.port))
+            .with_context(|| format!("Failed to connect to {}:{}", self.host, self.port))?;
+
+        let mut session = Session::new().context("Failed to create SSH session")?;
+        if let Some(timeout) = timeout {
+            session.set_timeout(timeout.as_millis().min(u32::MAX as u128) as u32);
+        }
+        session.set_tcp_stream(tcp);
+        session.handshake().context("SSH handshake failed")?;
+
+        match &self.auth {
+            SshAuth::Password(password) => session
+                .userauth_password(&self.username, password)
+                .context("SSH password authentication failed")?,
+            SshAuth::PrivateKeyFile { path, passphrase } => session
+                .userauth_pubkey_file(&self.username, None, path, passphrase.as_deref())
+                .context("SSH public key authentication failed")?,
+        }
+
+        Ok(session)
+    }
+
+    fn remote_temp_dir(&self, session: &Session) -> Result<PathBuf> {
+        let sftp = session.sftp().context("Failed to start SFTP subsystem")?;
+        let unique = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let remote_root = PathBuf::from(format!(
+            "/tmp/sandbox-rs-{}-{}",
+            std::process::id(),
+            unique
+        ));
+        sftp.mkdir(&remote_root, 0o700)
+            .with_context(|| format!("Failed to create remote temp dir: {:?}", remote_root))?;
+        Ok(remote_root)
+    }
+
+    fn upload_files<P: AsRef<Path>>(
+        &self,
+        session: &Session,
+        files: &[P],
+        working_dir: &Path,
+        remote_root: &Path,
+    ) -> Result<()> {
+        let sftp = session.sftp().context("Failed to start SFTP subsystem")?;
+
+        for file in files {
+            let file_path = file.as_ref();
+            let relative_path = file_path.strip_prefix(working_dir).with_context(|| {
+                format!("File {:?} is not within the working directory", file_path)
+            })?;
+            let remote_path = remote_root.join(relative_path);
+
+            if let Some(parent) = remote_path.parent() {
+                mkdir_p(&sftp, parent)?;
+            }
+
+            let contents = std::fs::read(file_path)
+                .with_context(|| format!("Failed to read file: {:?}", file_path))?;
+            let mut remote_file = sftp
+                .create(&remote_path)
+                .with_context(|| format!("Failed to create remote file: {:?}", remote_path))?;
+            remote_file
+                .write_all(&contents)
+                .with_context(|| format!("Failed to upload file: {:?}", remote_path))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn mkdir_p(sftp: &ssh2::Sftp, dir: &Path) -> Result<()> {
+    if sftp.stat(dir).is_ok() {
+        return Ok(());
+    }
+    if let Some(parent) = dir.parent() {
+        mkdir_p(sftp, parent)?;
+    }
+    // Another call may have already created it (e.g. a sibling file's
+    // parent); that's not an error.
+    match sftp.mkdir(dir, 0o700) {
+        Ok(()) => Ok(()),
+        Err(_) if sftp.stat(dir).is_ok() => Ok(()),
+        Err(err) => Err(err).with_context(|| format!("Failed to create remote directory: {:?}", dir)),
+    }
+}
+
+impl CommandExecutor for SshCommandExecutor {
+    fn execute<P: AsRef<Path>>(
+        &self,
+        command: &[String],
+        files: &[P],
+        working_dir: &Path,
+        options: ExecutionOptions,
+    ) -> Result<SandboxOutput> {
+        // The remote side has no `pre_exec` hook to apply a namespace and
+        // drop privileges in-process; honoring `options.policy` fully would
+        // mean shelling out to `sudo`/`setpriv` on the remote host, which is
+        // out of scope here. It's accepted for interface parity with
+        // `LinuxCommandExecutor` but otherwise unused.
+        let ExecutionOptions {
+            policy: _policy,
+            environment,
+            stdin,
+            timeout,
+        } = options;
+
+        let session = self.connect(timeout)?;
+        let remote_root = self.remote_temp_dir(&session)?;
+        let result = self
+            .upload_files(&session, files, working_dir, &remote_root)
+            .and_then(|()| run_remote_command(&session, command, &remote_root, &environment, stdin));
+
+        // Best-effort cleanup: leaving a stray temp dir behind is worse than
+        // ignoring a cleanup failure here.
+        if let Ok(sftp) = session.sftp() {
+            let _ = remove_remote_dir(&sftp, &remote_root);
+        }
+
+        result
+    }
+}
+
+fn run_remote_command(
+    session: &Session,
+    command: &[String],
+    remote_root: &Path,
+    environment: &Environment,
+    stdin: Option<Vec<u8>>,
+) -> Result<SandboxOutput> {
+    let mut channel = session.channel_session().context("Failed to open SSH channel")?;
+
+    let remote_command = build_remote_command(command, remote_root, environment);
+    channel
+        .exec(&remote_command)
+        .context("Failed to exec remote command")?;
+
+    if let Some(input) = stdin {
+        channel
+            .write_all(&input)
+            .context("Failed to write to remote stdin")?;
+    }
+    channel
+        .send_eof()
+        .context("Failed to send EOF to remote command")?;
+
+    let mut stdout = Vec::new();
+    channel
+        .read_to_end(&mut stdout)
+        .context("Failed to read remote stdout")?;
+    let mut stderr = Vec::new();
+    channel
+        .stderr()
+        .read_to_end(&mut stderr)
+        .context("Failed to read remote stderr")?;
+
+    channel.wait_close().context("Failed to close remote channel")?;
+    let exit_status = channel
+        .exit_status()
+        .context("Failed to read remote exit status")?;
+
+    Ok(SandboxOutput {
+        status: ExitStatus::from_raw(exit_status << 8),
+        stdout,
+        stderr,
+    })
+}
+
+/// Builds the shell command line run on the remote host: `cd` into the
+/// uploaded temp dir, apply the environment policy, then run the command.
+///
+/// The `cd` has to be joined to the rest with `&&` rather than a plain
+/// space — otherwise the remote shell parses the whole line as a single
+/// `cd` invocation with the env assignments and command as extra operands,
+/// which fails before the target command ever runs.
+fn build_remote_command(command: &[String], remote_root: &Path, environment: &Environment) -> String {
+    let cd_part = format!("cd {}", shell_quote(&remote_root.to_string_lossy()));
+
+    let mut rest = Vec::new();
+    match environment {
+        Environment::InheritAll => {}
+        Environment::ClearAll(vars) => {
+            rest.push("env -i".to_string());
+            for (key, value) in vars {
+                rest.push(shell_quote(&format!("{}={}", key, value)));
+            }
+        }
+        Environment::InheritWithAllowlist(names) => {
+            rest.push("env -i".to_string());
+            for name in names {
+                if let Ok(value) = std::env::var(name) {
+                    rest.push(shell_quote(&format!("{}={}", name, value)));
+                }
+            }
+        }
+    }
+    rest.extend(command.iter().map(|arg| shell_quote(arg)));
+
+    format!("{} && {}", cd_part, rest.join(" "))
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn remove_remote_dir(sftp: &ssh2::Sftp, dir: &Path) -> Result<()> {
+    for entry in sftp.readdir(dir)? {
+        let (path, stat) = entry;
+        if stat.is_dir() {
+            remove_remote_dir(sftp, &path)?;
+        } else {
+            sftp.unlink(&path)?;
+        }
+    }
+    sftp.rmdir(dir)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shell_quote_escapes_single_quotes() {
+        assert_eq!(shell_quote("plain"), "'plain'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn build_remote_command_joins_cd_with_and_and() {
+        let command = vec!["cat".to_string(), "file.txt".to_string()];
+        let line = build_remote_command(&command, Path::new("/tmp/sandbox"), &Environment::InheritAll);
+
+        assert_eq!(line, "cd '/tmp/sandbox' && 'cat' 'file.txt'");
+    }
+
+    #[test]
+    fn build_remote_command_clears_and_sets_env() {
+        let command = vec!["env".to_string()];
+        let mut vars = std::collections::HashMap::new();
+        vars.insert("FOO".to_string(), "bar".to_string());
+        let line = build_remote_command(&command, Path::new("/tmp/sandbox"), &Environment::ClearAll(vars));
+
+        assert_eq!(line, "cd '/tmp/sandbox' && env -i 'FOO=bar' 'env'");
+    }
+
+    #[test]
+    fn build_remote_command_quotes_a_malicious_env_var_name() {
+        let command = vec!["env".to_string()];
+        let mut vars = std::collections::HashMap::new();
+        vars.insert("X; rm -rf /tmp".to_string(), "v".to_string());
+        let line = build_remote_command(&command, Path::new("/tmp/sandbox"), &Environment::ClearAll(vars));
+
+        assert_eq!(
+            line,
+            "cd '/tmp/sandbox' && env -i 'X; rm -rf /tmp=v' 'env'"
+        );
+    }
+}